@@ -10,38 +10,24 @@ fn run_oram_benchmark(num_blocks: u32, bucket_capacity: usize, block_size: usize
     // Initialize the ORAM
     let mut oram = PathORAM::new(num_blocks, bucket_capacity);
 
-    // Stash statistics: map each stash size to the number of accesses where it was strictly > that size
-    let mut stash_sizes: Vec<u32> = vec![0; total_accesses as usize];
-
-    // Access the ORAM sequentially and log stash sizes after warmup period
-    for access_count in 0..total_accesses {
+    // Warm up, then discard the warmup period's stats so only steady-state
+    // accesses feed the stash-size histogram.
+    for access_count in 0..warmup_accesses {
         let block_id = (access_count % num_blocks) + 1;
-        oram.access("read", block_id, None);
-
-        // Start collecting stash size statistics after warmup period
-        if access_count >= warmup_accesses {
-            let stash_size = oram.stash.len();
-            stash_sizes[stash_size] += 1;
-        }
+        oram.access("read", block_id, None).expect("tree is not corrupted during the benchmark");
     }
+    oram.reset_stats();
 
-    // Calculate the stash size data to write to file
-    let mut stash_data: Vec<(i32, u32)> = Vec::new();
-    let mut running_sum = stash_sizes.iter().sum::<u32>();
-    stash_data.push((-1_i32, total_accesses - warmup_accesses)); // First line: -1, total number of accesses
-
-    for i in 0..(stash_sizes.len() as i32) {
-        stash_data.push((i, running_sum));
-        running_sum -= stash_sizes[i as usize];
+    for access_count in warmup_accesses..total_accesses {
+        let block_id = (access_count % num_blocks) + 1;
+        oram.access("read", block_id, None).expect("tree is not corrupted during the benchmark");
     }
 
-    // Write the stash size data to a text file
+    oram.verify_tree().expect("tree integrity check failed after the benchmark run");
+
+    // Write the stash-size overflow-probability table to a text file
     let mut file = File::create(format!("stash_data_N{}_Z{}_B{}.txt", num_blocks, bucket_capacity, block_size))?;
-    for (i, count) in stash_data {
-        if count > 0 {
-            writeln!(file, "{},{}", i, count)?;
-        }
-    }
+    write!(file, "{}", oram.stats().to_csv())?;
 
     Ok(())
 }
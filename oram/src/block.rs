@@ -0,0 +1,241 @@
+use std::fmt;
+
+/// On-disk size, in bytes, reserved for a single encoded block.
+///
+/// Every block, real or dummy, is padded to exactly this many bytes so that
+/// buckets have a fixed size on disk, matching the Path ORAM requirement
+/// that equal-sized slots reveal nothing about their contents. A block
+/// whose encoded, compressed form doesn't fit is rejected rather than
+/// silently truncated.
+pub const BLOCK_SIZE: usize = 64;
+
+/// Length, in bytes, of the [`Block::to_padded_bytes`] header: a 1-byte
+/// [`CompressionType`] tag followed by a 4-byte little-endian payload length.
+const HEADER_LEN: usize = 5;
+
+/// Errors raised while encoding, compressing, or decoding a block.
+#[derive(Debug)]
+pub enum BlockError {
+    /// The encoded, compressed block doesn't fit in `BLOCK_SIZE` bytes.
+    PayloadTooLarge { encoded_len: usize, limit: usize },
+    /// Fewer bytes were available than the format requires.
+    Truncated,
+    /// The compression tag stored in a block's header is not recognized.
+    UnknownCompressionTag(u8),
+    /// The compressed payload could not be decompressed.
+    Decompression(String),
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockError::PayloadTooLarge { encoded_len, limit } => write!(
+                f,
+                "encoded block of {encoded_len} bytes exceeds the {limit}-byte limit"
+            ),
+            BlockError::Truncated => write!(f, "block buffer is truncated"),
+            BlockError::UnknownCompressionTag(tag) => {
+                write!(f, "unknown compression tag {tag}")
+            }
+            BlockError::Decompression(msg) => write!(f, "decompression failed: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for BlockError {}
+
+/// Compression codec applied to a block's payload before it is padded and
+/// written into a bucket slot, and reversed on read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompressionType {
+    /// Store the payload as-is.
+    None,
+    /// Compress with `lz4_flex`.
+    Lz4,
+    /// Compress with `miniz_oxide`'s DEFLATE implementation at the given level (0-10).
+    Miniz(u8),
+}
+
+impl CompressionType {
+    fn tag(self) -> u8 {
+        match self {
+            CompressionType::None => 0,
+            CompressionType::Lz4 => 1,
+            CompressionType::Miniz(_) => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Result<CompressionType, BlockError> {
+        match tag {
+            0 => Ok(CompressionType::None),
+            1 => Ok(CompressionType::Lz4),
+            // The compression level only matters when compressing, so any
+            // value decodes fine here.
+            2 => Ok(CompressionType::Miniz(0)),
+            other => Err(BlockError::UnknownCompressionTag(other)),
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            CompressionType::None => data.to_vec(),
+            CompressionType::Lz4 => lz4_flex::compress_prepend_size(data),
+            CompressionType::Miniz(level) => miniz_oxide::deflate::compress_to_vec(data, level),
+        }
+    }
+
+    fn decompress(tag: u8, data: &[u8]) -> Result<Vec<u8>, BlockError> {
+        match Self::from_tag(tag)? {
+            CompressionType::None => Ok(data.to_vec()),
+            CompressionType::Lz4 => lz4_flex::decompress_size_prepended(data)
+                .map_err(|e| BlockError::Decompression(e.to_string())),
+            CompressionType::Miniz(_) => miniz_oxide::inflate::decompress_to_vec(data)
+                .map_err(|e| BlockError::Decompression(format!("{e:?}"))),
+        }
+    }
+}
+
+/// Length-prefixed binary serialization, modeled on the `coding::Encode`
+/// trait in fjall's lsm-tree.
+pub trait Encode {
+    fn encode(&self) -> Vec<u8>;
+}
+
+/// The inverse of [`Encode`].
+pub trait Decode: Sized {
+    fn decode(buf: &[u8]) -> Result<Self, BlockError>;
+}
+
+/// A single block of client data tracked by the ORAM.
+#[derive(Clone, Debug)]
+pub struct Block {
+    pub block_id: u32,
+    pub data: Vec<u8>,
+}
+
+impl Encode for Block {
+    /// Serializes as `block_id (u32) || payload_len (u32) || payload`.
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(8 + self.data.len());
+        buf.extend_from_slice(&self.block_id.to_le_bytes());
+        buf.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&self.data);
+        buf
+    }
+}
+
+impl Decode for Block {
+    fn decode(buf: &[u8]) -> Result<Self, BlockError> {
+        if buf.len() < 8 {
+            return Err(BlockError::Truncated);
+        }
+        let block_id = u32::from_le_bytes(buf[0..4].try_into().unwrap());
+        let payload_len = u32::from_le_bytes(buf[4..8].try_into().unwrap()) as usize;
+        let data = buf
+            .get(8..8 + payload_len)
+            .ok_or(BlockError::Truncated)?
+            .to_vec();
+        Ok(Block { block_id, data })
+    }
+}
+
+impl Block {
+    /// Encodes, compresses, and pads this block to the fixed `BLOCK_SIZE`
+    /// on-disk representation, so every bucket slot is indistinguishable
+    /// regardless of the block's real payload length.
+    pub fn to_padded_bytes(
+        &self,
+        compression: CompressionType,
+    ) -> Result<[u8; BLOCK_SIZE], BlockError> {
+        let encoded = self.encode();
+        let compressed = compression.compress(&encoded);
+
+        if HEADER_LEN + compressed.len() > BLOCK_SIZE {
+            return Err(BlockError::PayloadTooLarge {
+                encoded_len: compressed.len(),
+                limit: BLOCK_SIZE - HEADER_LEN,
+            });
+        }
+
+        let mut buf = [0u8; BLOCK_SIZE];
+        buf[0] = compression.tag();
+        buf[1..5].copy_from_slice(&(compressed.len() as u32).to_le_bytes());
+        buf[HEADER_LEN..HEADER_LEN + compressed.len()].copy_from_slice(&compressed);
+        Ok(buf)
+    }
+
+    /// Decompresses and decodes a block previously written by
+    /// [`Block::to_padded_bytes`].
+    pub fn from_padded_bytes(buf: &[u8; BLOCK_SIZE]) -> Result<Self, BlockError> {
+        let tag = buf[0];
+        let compressed_len = u32::from_le_bytes(buf[1..5].try_into().unwrap()) as usize;
+        let compressed = buf
+            .get(HEADER_LEN..HEADER_LEN + compressed_len)
+            .ok_or(BlockError::Truncated)?;
+        let encoded = CompressionType::decompress(tag, compressed)?;
+        Block::decode(&encoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_padded_round_trip_none() {
+        let block = Block {
+            block_id: 3,
+            data: vec![1, 2, 3, 4],
+        };
+        let padded = block.to_padded_bytes(CompressionType::None).unwrap();
+        let decoded = Block::from_padded_bytes(&padded).unwrap();
+        assert_eq!(decoded.block_id, block.block_id);
+        assert_eq!(decoded.data, block.data);
+    }
+
+    #[test]
+    fn test_padded_round_trip_lz4() {
+        let block = Block {
+            block_id: 5,
+            data: vec![7u8; 20],
+        };
+        let padded = block.to_padded_bytes(CompressionType::Lz4).unwrap();
+        let decoded = Block::from_padded_bytes(&padded).unwrap();
+        assert_eq!(decoded.block_id, block.block_id);
+        assert_eq!(decoded.data, block.data);
+    }
+
+    #[test]
+    fn test_padded_round_trip_miniz() {
+        let block = Block {
+            block_id: 9,
+            data: vec![7u8; 20],
+        };
+        let padded = block.to_padded_bytes(CompressionType::Miniz(6)).unwrap();
+        let decoded = Block::from_padded_bytes(&padded).unwrap();
+        assert_eq!(decoded.block_id, block.block_id);
+        assert_eq!(decoded.data, block.data);
+    }
+
+    #[test]
+    fn test_to_padded_bytes_rejects_oversized_payload() {
+        let block = Block {
+            block_id: 1,
+            data: vec![0u8; BLOCK_SIZE * 2],
+        };
+        let err = block.to_padded_bytes(CompressionType::None).unwrap_err();
+        assert!(matches!(err, BlockError::PayloadTooLarge { .. }));
+    }
+
+    #[test]
+    fn test_decode_rejects_truncated_buffer() {
+        let err = Block::decode(&[0u8; 4]).unwrap_err();
+        assert!(matches!(err, BlockError::Truncated));
+    }
+
+    #[test]
+    fn test_decompress_rejects_unknown_compression_tag() {
+        let err = CompressionType::decompress(99, &[]).unwrap_err();
+        assert!(matches!(err, BlockError::UnknownCompressionTag(99)));
+    }
+}
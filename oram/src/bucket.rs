@@ -1,48 +1,262 @@
-use crate::block::Block;
-use std::collections::HashMap;
+use crate::block::{Block, BlockError, CompressionType, Encode, BLOCK_SIZE};
+use std::fmt;
+use xxhash_rust::xxh3::xxh3_64;
 
-/// A bucket within the ORAM tree structure that stores blocks by their IDs.
+/// Trailing bytes appended to a bucket's on-disk slot to hold its checksum.
+const CHECKSUM_LEN: usize = 8;
+
+/// Per-slot occupancy flag prepended to a block's on-disk representation,
+/// so a free slot is distinguished from an occupied one without relying on
+/// a reserved `block_id`.
+const OCCUPIED_FLAG_LEN: usize = 1;
+
+/// Returned by [`Bucket::add_block`] when every slot is already occupied.
+#[derive(Debug)]
+pub struct BucketFullError;
+
+impl fmt::Display for BucketFullError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bucket is full")
+    }
+}
+
+impl std::error::Error for BucketFullError {}
+
+/// A bucket within the ORAM tree structure.
+///
+/// Blocks live in a fixed-size `Vec<Option<Block>>`: a `None` slot is free,
+/// a `Some` slot is occupied. This makes occupancy explicit metadata rather
+/// than relying on a reserved `block_id` sentinel, so block id `0` is a
+/// perfectly ordinary id, and checking/toggling a slot's occupancy is O(1).
 #[derive(Clone, Debug)]
 pub struct Bucket {
-    pub blocks: HashMap<u32, Block>, // Maps block IDs to blocks for efficient access
-    pub capacity: usize,              // Maximum number of blocks per bucket
+    slots: Vec<Option<Block>>,
+    pub capacity: usize,               // Maximum number of blocks per bucket
+    pub compression: CompressionType,  // Codec applied to blocks when serialized to disk
+    pub checksum: u64,                 // xxh3 checksum over the canonical contents
 }
 
 impl Bucket {
-    /// Creates a new bucket with a specified capacity.
-    pub fn new(capacity: usize) -> Self {
-        Bucket {
-            blocks: HashMap::new(),
+    /// Creates a new, empty bucket with a specified capacity and on-disk compression codec.
+    pub fn new(capacity: usize, compression: CompressionType) -> Self {
+        let mut bucket = Bucket {
+            slots: vec![None; capacity],
             capacity,
-        }
+            compression,
+            checksum: 0,
+        };
+        bucket.checksum = bucket.compute_checksum();
+        bucket
     }
 
-    /// Adds a block to the bucket, respecting the capacity limit.
-    /// If the bucket is full, an existing block is removed to make space.
-    pub fn add_block(&mut self, block: Block) {
-        if self.blocks.len() >= self.capacity {
-            // Remove a random block to maintain the bucket capacity
-            let first_key = *self.blocks.keys().next().unwrap();
-            self.blocks.remove(&first_key);
-        }
-        self.blocks.insert(block.block_id, block);
+    /// Whether slot `ix` holds no block.
+    pub fn is_free(&self, ix: usize) -> bool {
+        self.slots[ix].is_none()
+    }
+
+    /// Marks slot `ix` as occupied by `block`, overwriting whatever was there.
+    pub fn occupy(&mut self, ix: usize, block: Block) {
+        self.slots[ix] = Some(block);
+    }
+
+    /// Marks slot `ix` as free.
+    pub fn free(&mut self, ix: usize) {
+        self.slots[ix] = None;
+    }
+
+    /// Adds a block to the first free slot.
+    ///
+    /// Returns [`BucketFullError`] if every slot is already occupied, rather
+    /// than silently evicting a real block to make room.
+    pub fn add_block(&mut self, block: Block) -> Result<(), BucketFullError> {
+        let free_ix = self.slots.iter().position(Option::is_none).ok_or(BucketFullError)?;
+        self.occupy(free_ix, block);
+        Ok(())
     }
 
     /// Retrieves a block by its ID, if it exists.
     pub fn get_block(&self, block_id: u32) -> Option<&Block> {
-        self.blocks.get(&block_id)
+        self.slots
+            .iter()
+            .flatten()
+            .find(|block| block.block_id == block_id)
     }
 
-    /// Returns all blocks in the bucket as a vector (useful for reading paths).
+    /// Returns all occupied blocks in the bucket as a vector (useful for reading paths).
     pub fn get_all_blocks(&self) -> Vec<Block> {
-        self.blocks.values().cloned().collect()
+        self.slots.iter().flatten().cloned().collect()
     }
 
-    /// Clears the bucket and refills it with selected blocks up to its capacity.
+    /// Clears the bucket and refills it with selected blocks up to its capacity,
+    /// then recomputes the bucket's checksum over the new canonical contents.
     pub fn replace_blocks(&mut self, new_blocks: Vec<Block>) {
-        self.blocks.clear();
-        for block in new_blocks.into_iter().take(self.capacity) {
-            self.blocks.insert(block.block_id, block);
+        for slot in self.slots.iter_mut() {
+            *slot = None;
+        }
+        for (ix, block) in new_blocks.into_iter().take(self.capacity).enumerate() {
+            self.slots[ix] = Some(block);
+        }
+        self.checksum = self.compute_checksum();
+    }
+
+    /// The occupied blocks, sorted by `block_id`, followed by `None` for
+    /// each free slot up to `capacity`. Hashing this canonical form, rather
+    /// than raw slot order, makes the checksum independent of which
+    /// physical slot a block happens to occupy.
+    fn canonical_blocks(&self) -> Vec<Option<&Block>> {
+        let mut occupied: Vec<&Block> = self.slots.iter().flatten().collect();
+        occupied.sort_by_key(|block| block.block_id);
+        let mut canonical: Vec<Option<&Block>> = occupied.into_iter().map(Some).collect();
+        canonical.resize(self.capacity, None);
+        canonical
+    }
+
+    /// Computes the xxh3 checksum over this bucket's canonical contents.
+    pub fn compute_checksum(&self) -> u64 {
+        let mut buf = Vec::new();
+        for slot in self.canonical_blocks() {
+            match slot {
+                Some(block) => {
+                    buf.push(1u8);
+                    buf.extend_from_slice(&block.encode());
+                }
+                None => buf.push(0u8),
+            }
+        }
+        xxh3_64(&buf)
+    }
+
+    /// The fixed size, in bytes, of this bucket's on-disk representation.
+    pub fn slot_size(capacity: usize) -> usize {
+        capacity * (OCCUPIED_FLAG_LEN + BLOCK_SIZE) + CHECKSUM_LEN
+    }
+
+    /// Serializes the bucket into its fixed-size on-disk slot: one
+    /// occupancy byte plus `BLOCK_SIZE` padded block bytes per capacity
+    /// slot, followed by the stored checksum.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BlockError> {
+        let mut buf = Vec::with_capacity(Self::slot_size(self.capacity));
+        for slot in &self.slots {
+            match slot {
+                Some(block) => {
+                    buf.push(1u8);
+                    buf.extend_from_slice(&block.to_padded_bytes(self.compression)?);
+                }
+                None => {
+                    buf.push(0u8);
+                    buf.extend_from_slice(&[0u8; BLOCK_SIZE]);
+                }
+            }
+        }
+        buf.extend_from_slice(&self.checksum.to_le_bytes());
+        Ok(buf)
+    }
+
+    /// Deserializes a bucket previously written by [`Bucket::to_bytes`]. The
+    /// stored checksum is taken verbatim from the trailer, not recomputed,
+    /// so a corrupted or replayed slot can still be detected by comparing it
+    /// against [`Bucket::compute_checksum`].
+    pub fn from_bytes(buf: &[u8], capacity: usize, compression: CompressionType) -> Result<Self, BlockError> {
+        let mut bucket = Bucket::new(capacity, compression);
+        let slot_stride = OCCUPIED_FLAG_LEN + BLOCK_SIZE;
+        let (slots_buf, checksum_buf) = buf.split_at(capacity * slot_stride);
+        for (ix, chunk) in slots_buf.chunks_exact(slot_stride).enumerate() {
+            if chunk[0] == 1 {
+                let array: [u8; BLOCK_SIZE] = chunk[OCCUPIED_FLAG_LEN..].try_into().unwrap();
+                bucket.slots[ix] = Some(Block::from_padded_bytes(&array)?);
+            }
         }
+        bucket.checksum = u64::from_le_bytes(checksum_buf.try_into().unwrap());
+        Ok(bucket)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_block_fails_once_full() {
+        let mut bucket = Bucket::new(2, CompressionType::None);
+        bucket
+            .add_block(Block {
+                block_id: 1,
+                data: vec![],
+            })
+            .unwrap();
+        bucket
+            .add_block(Block {
+                block_id: 2,
+                data: vec![],
+            })
+            .unwrap();
+
+        let err = bucket
+            .add_block(Block {
+                block_id: 3,
+                data: vec![],
+            })
+            .unwrap_err();
+        assert!(matches!(err, BucketFullError));
+    }
+
+    #[test]
+    fn test_to_bytes_from_bytes_round_trip() {
+        let mut bucket = Bucket::new(2, CompressionType::None);
+        bucket
+            .add_block(Block {
+                block_id: 4,
+                data: vec![1, 2, 3],
+            })
+            .unwrap();
+        bucket.checksum = bucket.compute_checksum();
+
+        let bytes = bucket.to_bytes().unwrap();
+        let decoded = Bucket::from_bytes(&bytes, 2, CompressionType::None).unwrap();
+
+        assert_eq!(decoded.get_block(4).unwrap().data, vec![1, 2, 3]);
+        assert_eq!(decoded.checksum, bucket.checksum);
+        assert_eq!(decoded.checksum, decoded.compute_checksum());
+    }
+
+    #[test]
+    fn test_checksum_detects_tampering() {
+        let mut bucket = Bucket::new(2, CompressionType::None);
+        bucket
+            .add_block(Block {
+                block_id: 1,
+                data: vec![1],
+            })
+            .unwrap();
+        bucket.checksum = bucket.compute_checksum();
+
+        // Swap in a different block without going through `replace_blocks`,
+        // so the stored checksum is now stale.
+        bucket.occupy(0, Block { block_id: 1, data: vec![2] });
+
+        assert_ne!(bucket.checksum, bucket.compute_checksum());
+    }
+
+    #[test]
+    fn test_replace_blocks_recomputes_checksum() {
+        let mut bucket = Bucket::new(2, CompressionType::None);
+        bucket.replace_blocks(vec![Block {
+            block_id: 1,
+            data: vec![1],
+        }]);
+        assert_eq!(bucket.checksum, bucket.compute_checksum());
+    }
+
+    #[test]
+    fn test_checksum_independent_of_slot_order() {
+        let mut a = Bucket::new(2, CompressionType::None);
+        a.occupy(0, Block { block_id: 1, data: vec![1] });
+        a.occupy(1, Block { block_id: 2, data: vec![2] });
+
+        let mut b = Bucket::new(2, CompressionType::None);
+        b.occupy(0, Block { block_id: 2, data: vec![2] });
+        b.occupy(1, Block { block_id: 1, data: vec![1] });
+
+        assert_eq!(a.compute_checksum(), b.compute_checksum());
     }
 }
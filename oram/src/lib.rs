@@ -0,0 +1,6 @@
+pub mod block;
+pub mod bucket;
+pub mod oram;
+pub mod position_map;
+pub mod stats;
+pub mod storage;
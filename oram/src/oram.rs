@@ -1,95 +1,259 @@
-use crate::{block::Block, bucket::Bucket};
+use crate::{
+    block::{Block, BlockError, CompressionType},
+    position_map::PositionMap,
+    stats::{OramStats, OramStatsSnapshot},
+    storage::{MemoryStorage, TreeStorage},
+};
+#[cfg(feature = "flat-position-map")]
+use crate::position_map::HashMapPositionMap;
 use rand::Rng;
 use std::collections::HashMap;
+use std::fmt;
 #[cfg(feature = "parallel")]
 use rayon::prelude::*;
 
+/// Errors raised while accessing or verifying a `PathORAM`.
+#[derive(Debug)]
+pub enum OramError {
+    /// A bucket's stored checksum didn't match its recomputed contents,
+    /// meaning the server corrupted, replayed, or tampered with it.
+    ChecksumMismatch { bucket_index: usize },
+    /// A block couldn't be encoded into the fixed on-disk slot size.
+    Block(BlockError),
+}
+
+impl fmt::Display for OramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OramError::ChecksumMismatch { bucket_index } => {
+                write!(f, "checksum mismatch at bucket index {bucket_index}")
+            }
+            OramError::Block(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for OramError {}
+
+impl From<BlockError> for OramError {
+    fn from(err: BlockError) -> Self {
+        OramError::Block(err)
+    }
+}
+
 /// Structure for the Path ORAM
 pub struct PathORAM {
-    pub(crate) tree: Vec<Bucket>,
-    pub(crate) position_map: HashMap<u32, u32>, // Maps each block to a position in the tree
+    pub(crate) tree: Box<dyn TreeStorage>,
+    pub(crate) position_map: Box<dyn PositionMap>, // Maps each block to a position in the tree
     pub stash: HashMap<u32, Block>,      // Stash for blocks that couldn't be evicted
+    /// Positions of blocks currently in the stash, cached from whenever they
+    /// were last looked up in `position_map` so that evicting the stash
+    /// doesn't re-query `position_map` once per level for every block it
+    /// holds (prohibitively expensive once `position_map` is itself a
+    /// `RecursivePositionMap`, since every query is a nested tree access).
+    stash_positions: HashMap<u32, u32>,
     pub(crate) capacity: usize,                 // Number of blocks per bucket
     pub(crate) tree_height: u32,                // Height of the ORAM tree
+    pub(crate) compression: CompressionType,    // Codec applied to blocks written to storage
+    pub(crate) num_blocks: u32,                 // Number of live data blocks
+    stats: OramStats,                           // Access and stash counters
 }
 
 impl PathORAM {
+    /// Builds a `PathORAM` for `num_blocks` blocks using the flat,
+    /// `O(N)`-memory `HashMapPositionMap`.
+    #[cfg(feature = "flat-position-map")]
     pub fn new(num_blocks: u32, capacity: usize) -> Self {
-        // Step 1: Calculate the tree height and number of buckets
-        let tree_height = (num_blocks as f64).log2().ceil() as u32; // L = ⌈log2(N)⌉
-        let num_buckets = (1 << (tree_height + 1)) - 1; // Total buckets for a complete binary tree
+        let position_map: Box<dyn PositionMap> = Box::new(HashMapPositionMap::new());
+        Self::with_position_map(num_blocks, capacity, position_map)
+    }
 
-        // Step 2: Initialize the ORAM tree with buckets filled with dummy blocks
-        let mut tree = Vec::with_capacity(num_buckets as usize);
+    /// Builds a `PathORAM` whose position map is itself a recursive Path
+    /// ORAM: `packing_factor` position entries are packed per position
+    /// block, recursing until a level needs `recursion_threshold` blocks or
+    /// fewer, at which point it falls back to a flat map. This is what
+    /// keeps the client-side index at `O(log N)` instead of `O(N)`.
+    pub fn new_recursive(
+        num_blocks: u32,
+        capacity: usize,
+        packing_factor: usize,
+        recursion_threshold: u32,
+    ) -> Self {
+        let position_map = crate::position_map::build(
+            num_blocks,
+            capacity,
+            packing_factor,
+            recursion_threshold,
+        );
+        Self::with_position_map(num_blocks, capacity, position_map)
+    }
 
-        #[cfg(feature = "parallel")]
-        for _ in 0..num_buckets {
-            let mut bucket = Bucket::new(capacity);
-            // Fill the bucket with dummy blocks
-            for _ in 0..capacity {
-                bucket.add_block(Block { block_id: 0, data: 0 });
-            }
-            tree.push(bucket);
-        }
+    /// Builds a `PathORAM` for `num_blocks` blocks, backed by the default
+    /// in-memory `MemoryStorage` and the given `position_map`. Used by `new`
+    /// and `new_recursive`, and by `RecursivePositionMap` itself to build
+    /// each smaller child `PathORAM`.
+    pub(crate) fn with_position_map(
+        num_blocks: u32,
+        capacity: usize,
+        position_map: Box<dyn PositionMap>,
+    ) -> Self {
+        let compression = CompressionType::None;
+        let tree: Box<dyn TreeStorage> = Box::new(MemoryStorage::new(
+            Self::num_buckets_for(num_blocks),
+            capacity,
+            compression,
+        ));
+        Self::with_storage(num_blocks, capacity, tree, position_map)
+    }
 
-        // Step 3: Initialize the position map with random positions
-        let mut position_map = HashMap::new();
+    /// Builds a `PathORAM` for `num_blocks` blocks backed by `tree` and
+    /// `position_map` directly. This is the fully general constructor: `new`
+    /// and `new_recursive` both delegate to it via the default in-memory
+    /// `MemoryStorage`, but a caller that needs a tree too large to fit in
+    /// RAM — e.g. an `MmapStorage` — constructs it and calls this directly.
+    ///
+    /// `tree` must already hold [`PathORAM::num_buckets_for`]`(num_blocks)`
+    /// buckets of `capacity` slots each; `MmapStorage::new` and
+    /// `MemoryStorage::new` both take `num_buckets` as a plain argument, so
+    /// callers size them with the same helper.
+    pub fn with_storage(
+        num_blocks: u32,
+        capacity: usize,
+        tree: Box<dyn TreeStorage>,
+        mut position_map: Box<dyn PositionMap>,
+    ) -> Self {
+        let tree_height = (num_blocks as f64).log2().ceil() as u32; // L = ⌈log2(N)⌉
+        let compression = CompressionType::None;
+
+        // Initialize the position map with random positions
         let mut rng = rand::thread_rng();
         #[cfg(feature = "parallel")]
         for block_id in 1..=num_blocks {
             let random_position = rng.gen_range(0..(1 << tree_height));
-            position_map.insert(block_id, random_position);
+            position_map
+                .set(block_id, random_position)
+                .expect("a freshly created position map cannot yet hold a corrupted child tree");
         }
 
-        // Step 4: Initialize PathORAM with the populated tree, position map, and stash
+        // Initialize PathORAM with the populated tree, position map, and stash
         let mut oram = PathORAM {
             tree,
             position_map,
             stash: HashMap::new(),
+            stash_positions: HashMap::new(),
             capacity,
             tree_height,
+            compression,
+            num_blocks,
+            stats: OramStats::new(),
         };
 
         for block_id in 1..=num_blocks {
-            oram.access("write", block_id, Some(0));
+            oram.access("write", block_id, Some(Vec::new()))
+                .expect("initial fill writes a freshly created tree with no corruption");
         }
 
         oram
     }
 
-    pub fn access(&mut self, op: &str, block_id: u32, new_data: Option<u32>) -> Option<u32> {
+    /// The number of buckets a complete Path ORAM tree needs to hold
+    /// `num_blocks` blocks: `2 * (1 << ceil(log2(num_blocks))) - 1`. Used to
+    /// size a `TreeStorage` (e.g. `MmapStorage::new`) before handing it to
+    /// [`PathORAM::with_storage`].
+    pub fn num_buckets_for(num_blocks: u32) -> usize {
+        let tree_height = (num_blocks as f64).log2().ceil() as u32;
+        ((1 << (tree_height + 1)) - 1) as usize
+    }
+
+    pub fn access(
+        &mut self,
+        op: &str,
+        block_id: u32,
+        new_data: Option<Vec<u8>>,
+    ) -> Result<Option<Vec<u8>>, OramError> {
+        let is_write = op == "write";
+        self.access_impl(op, block_id, |_old_data| {
+            if is_write {
+                Some(new_data.unwrap())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Reads `block_id`'s current data, derives its replacement via
+    /// `compute`, and writes the result back — all within the single tree
+    /// traversal `access` already performs, rather than one traversal to read
+    /// the old value and a second to write the new one.
+    pub fn update(
+        &mut self,
+        block_id: u32,
+        compute: impl FnOnce(Option<Vec<u8>>) -> Vec<u8>,
+    ) -> Result<Option<Vec<u8>>, OramError> {
+        self.access_impl("write", block_id, |old_data| Some(compute(old_data)))
+    }
+
+    /// Shared by `access` and `update`: performs one read-path/write-path
+    /// traversal, handing the block's current data (if any) to `resolve` and
+    /// writing back whatever it returns, or leaving the block unchanged if it
+    /// returns `None`.
+    fn access_impl(
+        &mut self,
+        _op: &str,
+        block_id: u32,
+        resolve: impl FnOnce(Option<Vec<u8>>) -> Option<Vec<u8>>,
+    ) -> Result<Option<Vec<u8>>, OramError> {
         let mut rng = rand::thread_rng();
+        #[cfg(feature = "stats")]
+        self.stats.record_op(_op);
 
         // Step 1: Retrieve the current position of the block and generate a new position
-        let old_position = *self.position_map.get(&block_id).unwrap();  // x in the algorithm
+        let old_position = self.position_map.get(block_id)?;             // x in the algorithm
         let new_position = rng.gen_range(0..(1 << self.tree_height));   // New position for the block
+        self.stash_positions.insert(block_id, old_position);
 
         // Step 2: Read the path from root to leaf based on the old position
         for level in 0..=self.tree_height {
             let index = self.get_bucket_index(old_position, level);
-            println!("Reading bucket at index {}", index);
-            for block in self.tree[index].get_all_blocks() {
-                if block.block_id != 0 { // Skip dummy blocks
-                    self.stash.insert(block.block_id, block);
+            let bucket = self.tree.read_bucket(index);
+            #[cfg(feature = "stats")]
+            self.stats.record_bucket_touched();
+            if bucket.checksum != bucket.compute_checksum() {
+                return Err(OramError::ChecksumMismatch { bucket_index: index });
+            }
+            for block in bucket.get_all_blocks() {
+                // The target block's position is already known above; every
+                // other block surfaces here for the first time since it was
+                // last evicted into the tree (which dropped its cached
+                // position), so this is the one time its position is looked up.
+                if block.block_id != block_id && !self.stash_positions.contains_key(&block.block_id) {
+                    let position = self.position_map.get(block.block_id)?;
+                    self.stash_positions.insert(block.block_id, position);
                 }
+                self.stash.insert(block.block_id, block);
             }
         }
 
-        println!("Stash size: {}", self.stash.len());
-
         // Step 3: Retrieve or update the block data in the stash
-        let mut data = self.stash.get(&block_id).map(|block| block.data);
-        if op == "write" {
-            let new_data_value = new_data.unwrap();
-            println!("Writing block {} with data {} to position {}", block_id, new_data_value, new_position);
-            self.stash.insert(block_id, Block {
+        let data = self.stash.get(&block_id).map(|block| block.data.clone());
+        if let Some(new_data_value) = resolve(data.clone()) {
+            let new_block = Block {
                 block_id,
                 data: new_data_value,
-            });
+            };
+            // Enforce the fixed on-disk slot size up front, regardless of
+            // which `TreeStorage` backend is in use: `MmapStorage` would
+            // reject an oversized block when it next serializes the bucket,
+            // but `MemoryStorage` never serializes at all, so without this
+            // check it would silently accept a payload that can't actually
+            // be written to disk.
+            new_block.to_padded_bytes(self.compression)?;
+            self.stash.insert(block_id, new_block);
         }
 
         // Step 4: Update position map and attempt to write path back to the tree
-        self.position_map.insert(block_id, new_position); // Update to new position
+        self.position_map.set(block_id, new_position)?; // Update to new position
+        self.stash_positions.insert(block_id, new_position);
 
         for level in (0..=self.tree_height).rev() {
             let index = self.get_bucket_index(old_position, level);
@@ -97,7 +261,7 @@ impl PathORAM {
             // Select blocks to write back to the current bucket
             let mut selected_blocks = Vec::with_capacity(self.capacity);
             for block in self.stash.values() {
-                if let Some(&pos) = self.position_map.get(&block.block_id) {
+                if let Some(&pos) = self.stash_positions.get(&block.block_id) {
                     if self.get_bucket_index(pos, level) == index {
                         selected_blocks.push(block.clone());
 
@@ -108,21 +272,103 @@ impl PathORAM {
                 }
             }
 
-            // Remove selected blocks from the stash
+            // Remove selected blocks from the stash; the tree is now the
+            // authoritative holder of their positions.
             for block in &selected_blocks {
                 self.stash.remove(&block.block_id);
+                self.stash_positions.remove(&block.block_id);
             }
 
-            // Pad with dummy blocks if needed
-            while selected_blocks.len() < self.capacity {
-                selected_blocks.push(Block { block_id: 0, data: 0 });
-            }
+            // Replace the bucket's contents with the selected blocks; any
+            // slots beyond `selected_blocks.len()` are left free.
+            #[cfg(feature = "stats")]
+            self.stats.record_eviction(!selected_blocks.is_empty());
+            let mut bucket = self.tree.read_bucket(index);
+            bucket.replace_blocks(selected_blocks);
+            self.tree.write_bucket(index, &bucket);
+        }
+
+        #[cfg(feature = "stats")]
+        self.stats.record_stash_size(self.stash.len());
+        Ok(data)
+    }
+
+    /// Returns a snapshot of this ORAM's access and stash counters.
+    pub fn stats(&self) -> OramStatsSnapshot {
+        self.stats.snapshot()
+    }
+
+    /// Clears the access and stash counters, e.g. to discard a warmup
+    /// period before measuring steady-state behavior.
+    pub fn reset_stats(&self) {
+        self.stats.reset()
+    }
+
+    /// Grows an initialized ORAM from `N` to `new_num_blocks` (`2N`) blocks
+    /// without rebuilding the tree from scratch, following Solana
+    /// `BucketMapConfig`'s capacity-pow2, grow-on-demand model.
+    ///
+    /// Every live block keeps its old path prefix and is given a freshly
+    /// sampled leaf in the enlarged `0..(1 << new_height)` range: for
+    /// `new_position = (old_position << 1) | random_bit`, the invariant
+    /// `get_bucket_index(new_position, level) == get_bucket_index(old_position, level)`
+    /// holds for every `level` up to the old tree height, so the block's
+    /// physical bucket doesn't move until a normal eviction sweep relocates
+    /// it. The migration is driven by issuing a dummy read for each live
+    /// block, which lazily drains it into the taller tree via the ordinary
+    /// stash/write-back path in [`PathORAM::access`].
+    ///
+    /// Only the existing block ids `1..=N` are repositioned and migrated.
+    /// The ids above `N` are given no position-map entry and are not
+    /// written, so they cannot be accessed after `resize`.
+    pub fn resize(&mut self, new_num_blocks: u32) -> Result<(), OramError> {
+        let new_height = (new_num_blocks as f64).log2().ceil() as u32;
+        assert_eq!(
+            new_height,
+            self.tree_height + 1,
+            "resize only supports growing from N to 2N blocks"
+        );
+        let new_num_buckets = (1u32 << (new_height + 1)) - 1;
+
+        self.tree.grow_to(new_num_buckets as usize);
 
-            // Replace the bucket's contents with the selected blocks
-            self.tree[index].replace_blocks(selected_blocks);
+        let mut rng = rand::thread_rng();
+        for block_id in 1..=self.num_blocks {
+            let old_position = self.position_map.get(block_id)?;
+            let extra_bit: u32 = rng.gen_range(0..2);
+            self.position_map.set(block_id, (old_position << 1) | extra_bit)?;
+        }
+
+        // Any block already sitting in the stash has a cached position from
+        // before the repositioning above, computed for the old tree height.
+        // Interpreting it under `new_height` would shift which path it names
+        // and could wrongly match (and permanently drop) it during the
+        // migration reads below, so drop the stale cache entirely; an absent
+        // entry is safely skipped by `access`'s eviction check, unlike a
+        // wrong one.
+        self.stash_positions.clear();
+
+        self.tree_height = new_height;
+
+        for block_id in 1..=self.num_blocks {
+            self.access("read", block_id, None)?;
         }
 
-        data
+        self.num_blocks = new_num_blocks;
+
+        Ok(())
+    }
+
+    /// Walks every bucket in the tree and reports the index of the first one
+    /// whose stored checksum doesn't match its recomputed contents.
+    pub fn verify_tree(&self) -> Result<(), OramError> {
+        for index in 0..self.tree.len() {
+            let bucket = self.tree.read_bucket(index);
+            if bucket.checksum != bucket.compute_checksum() {
+                return Err(OramError::ChecksumMismatch { bucket_index: index });
+            }
+        }
+        Ok(())
     }
 
     /// Helper function to calculate the bucket index at a given level for a specific leaf
@@ -144,10 +390,10 @@ mod tests {
         let mut oram = PathORAM::new(num_blocks, bucket_capacity);
 
         let block_id = 1;
-        let new_data = 42;
-        oram.access("write", block_id, Some(new_data));
+        let new_data = vec![42u8];
+        oram.access("write", block_id, Some(new_data.clone())).unwrap();
 
-        let read_data = oram.access("read", block_id, None).unwrap();
+        let read_data = oram.access("read", block_id, None).unwrap().unwrap();
         assert_eq!(read_data, new_data);
     }
 
@@ -157,45 +403,86 @@ mod tests {
         let bucket_capacity = 2;
         let warmup_accesses = 10000;
         let total_accesses = 10000 + warmup_accesses;
-    
+
         // Initialize the ORAM
         let mut oram = PathORAM::new(num_blocks, bucket_capacity);
-    
-        // Stash statistics: map each stash size to the number of accesses where it was strictly > that size
-        let mut stash_sizes: Vec<u32> = vec![0; total_accesses as usize];
-    
-        // Access the ORAM sequentially and log stash sizes after warmup period
-        for access_count in 0..total_accesses {
-            let block_id = (access_count % num_blocks) + 1;
-            oram.access("read", block_id, None);
 
-            // Start collecting stash size statistics after warmup period
-            if access_count >= warmup_accesses {
-                let stash_size = oram.stash.len();
-                stash_sizes[stash_size] += 1;
-            }
+        // Warm up, then discard the warmup period's stats
+        for access_count in 0..warmup_accesses {
+            let block_id = (access_count % num_blocks) + 1;
+            oram.access("read", block_id, None).unwrap();
         }
+        oram.reset_stats();
 
-        // Calculate the stash size data to write to file
-        let mut stash_data: Vec<(i32, u32)> = Vec::new();
-        let mut running_sum = stash_sizes.iter().sum::<u32>();
-        stash_data.push((-1_i32, total_accesses - warmup_accesses)); // First line: -1, total number of accesses
-    
-        for i in 0..(stash_sizes.len() as i32) {
-            stash_data.push((i, running_sum));
-            running_sum -= stash_sizes[i as usize];
+        for access_count in warmup_accesses..total_accesses {
+            let block_id = (access_count % num_blocks) + 1;
+            oram.access("read", block_id, None).unwrap();
         }
 
-        for (i, count) in stash_sizes.iter().enumerate() {
-            if count > &0 {
-                println!("{},{}", i, count);
+        println!("{}", oram.stats().to_csv());
+    }
+
+    #[test]
+    fn test_resize_preserves_blocks_with_nonempty_stash() {
+        let num_blocks = 16;
+        let bucket_capacity = 2;
+
+        // Repeat rather than seed: a capacity-2 tree leaves some blocks in
+        // the stash after most accesses (see test_stash_grows_unbounded), so
+        // a single run isn't guaranteed to catch `resize` mishandling a
+        // block that's resident in the stash rather than the tree.
+        for _ in 0..20 {
+            let mut oram = PathORAM::new(num_blocks, bucket_capacity);
+
+            for block_id in 1..=num_blocks {
+                oram.access("write", block_id, Some(vec![block_id as u8])).unwrap();
             }
-        }
 
-        for (i, count) in stash_data {
-            if count > 0 {
-                println!("{},{}", i, count);
+            for access_count in 0..2000u32 {
+                let block_id = (access_count % num_blocks) + 1;
+                oram.access("read", block_id, None).unwrap();
+            }
+
+            oram.resize(num_blocks * 2).unwrap();
+
+            for block_id in 1..=num_blocks {
+                let data = oram.access("read", block_id, None).unwrap().unwrap();
+                assert_eq!(data, vec![block_id as u8], "block {block_id} lost or corrupted by resize");
             }
         }
     }
+
+    #[test]
+    fn test_verify_tree_detects_checksum_mismatch() {
+        let mut oram = PathORAM::new(4, 4);
+        oram.access("write", 1, Some(vec![1])).unwrap();
+        assert!(oram.verify_tree().is_ok());
+
+        let mut bucket = oram.tree.read_bucket(0);
+        bucket.checksum ^= 1;
+        oram.tree.write_bucket(0, &bucket);
+
+        assert!(matches!(
+            oram.verify_tree(),
+            Err(OramError::ChecksumMismatch { bucket_index: 0 })
+        ));
+    }
+
+    #[test]
+    fn test_new_recursive_round_trips_blocks() {
+        let num_blocks = 64;
+        let bucket_capacity = 4;
+        // packing_factor=4, recursion_threshold=8 forces two levels of
+        // recursion (64 -> 16 -> 4) before the position map bottoms out.
+        let mut oram = PathORAM::new_recursive(num_blocks, bucket_capacity, 4, 8);
+
+        for block_id in 1..=num_blocks {
+            oram.access("write", block_id, Some(vec![block_id as u8])).unwrap();
+        }
+
+        for block_id in 1..=num_blocks {
+            let data = oram.access("read", block_id, None).unwrap().unwrap();
+            assert_eq!(data, vec![block_id as u8]);
+        }
+    }
 }
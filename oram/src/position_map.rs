@@ -0,0 +1,194 @@
+use crate::oram::{OramError, PathORAM};
+use std::collections::HashMap;
+
+/// Maps each data block id to its current position (leaf) in the ORAM
+/// tree. `get` and `set` mirror the two halves of `PathORAM::access` itself:
+/// look up where a block currently lives, then record the freshly sampled
+/// leaf it's been reassigned to.
+///
+/// Both return a `Result` because a `RecursivePositionMap` implements these
+/// by accessing a child `PathORAM`, and that access can surface an
+/// `OramError::ChecksumMismatch` from deep in the recursion; the flat
+/// `HashMapPositionMap` base case never actually fails.
+pub trait PositionMap {
+    /// Returns the block's current position.
+    fn get(&mut self, block_id: u32) -> Result<u32, OramError>;
+
+    /// Records the block's new position.
+    fn set(&mut self, block_id: u32, position: u32) -> Result<(), OramError>;
+}
+
+/// Flat, `O(N)` position map: one entry per block. This is the
+/// non-recursive case, appropriate only while `N` is small enough that the
+/// client can afford to hold one entry per block in memory.
+///
+/// This struct is always compiled in (not feature-gated) because it is also
+/// the unconditional base case that terminates `build`'s recursion; only the
+/// convenience `PathORAM::new` constructor that defaults to it is gated
+/// behind the `flat-position-map` feature.
+#[derive(Default)]
+pub struct HashMapPositionMap {
+    positions: HashMap<u32, u32>,
+}
+
+impl HashMapPositionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl PositionMap for HashMapPositionMap {
+    fn get(&mut self, block_id: u32) -> Result<u32, OramError> {
+        Ok(*self
+            .positions
+            .get(&block_id)
+            .expect("block_id must already have an assigned position"))
+    }
+
+    fn set(&mut self, block_id: u32, position: u32) -> Result<(), OramError> {
+        self.positions.insert(block_id, position);
+        Ok(())
+    }
+}
+
+/// Default number of packed position entries per recursive position-map
+/// block. Chosen so a packed entry array (`packing_factor` little-endian
+/// `u32`s) comfortably fits inside the fixed-size `Block` payload.
+pub const DEFAULT_PACKING_FACTOR: usize = 8;
+
+/// Default threshold below which recursion bottoms out into a flat
+/// `HashMapPositionMap`.
+pub const DEFAULT_RECURSION_THRESHOLD: u32 = 64;
+
+fn encode_positions(positions: &[u32]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(positions.len() * 4);
+    for position in positions {
+        buf.extend_from_slice(&position.to_le_bytes());
+    }
+    buf
+}
+
+fn decode_positions(buf: &[u8]) -> Vec<u32> {
+    buf.chunks_exact(4)
+        .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+        .collect()
+}
+
+/// Builds the position map for `num_blocks` data blocks: a flat map once
+/// `num_blocks` is at or below `recursion_threshold`, otherwise a
+/// [`RecursivePositionMap`] whose own child position map is built the same
+/// way, recursing until the threshold is reached.
+pub fn build(
+    num_blocks: u32,
+    bucket_capacity: usize,
+    packing_factor: usize,
+    recursion_threshold: u32,
+) -> Box<dyn PositionMap> {
+    // This base case must be unconditional: even with `RecursivePositionMap`
+    // validating its own arguments, gating termination itself behind a
+    // feature flag would make an un-featured build recurse forever instead
+    // of bottoming out.
+    if num_blocks <= recursion_threshold {
+        return Box::new(HashMapPositionMap::new());
+    }
+
+    Box::new(RecursivePositionMap::new(
+        num_blocks,
+        bucket_capacity,
+        packing_factor,
+        recursion_threshold,
+    ))
+}
+
+/// Standard recursive Path ORAM position map. Rather than one flat `O(N)`
+/// index, each data block's position is packed `packing_factor`-at-a-time
+/// into the blocks of a smaller child `PathORAM`, whose own position map is
+/// in turn flat or recursive depending on its size — so the client-side
+/// state needed to track positions shrinks every recursion level instead of
+/// growing linearly with `N`.
+pub struct RecursivePositionMap {
+    child: PathORAM,
+    packing_factor: u32,
+}
+
+impl RecursivePositionMap {
+    /// `packing_factor` position entries are packed per child block;
+    /// recursion bottoms out once a level's child needs `recursion_threshold`
+    /// blocks or fewer.
+    pub fn new(
+        num_blocks: u32,
+        bucket_capacity: usize,
+        packing_factor: usize,
+        recursion_threshold: u32,
+    ) -> Self {
+        assert!(
+            packing_factor >= 2,
+            "packing_factor must be at least 2, got {packing_factor}: a factor of 0 or 1 never \
+             shrinks the child position map, so build() would divide by zero or recurse forever"
+        );
+        assert!(
+            recursion_threshold >= 1,
+            "recursion_threshold must be at least 1, got {recursion_threshold}"
+        );
+
+        let packing_factor_u32 = packing_factor as u32;
+        let num_child_blocks = num_blocks.div_ceil(packing_factor_u32).max(1);
+
+        let child_position_map = build(
+            num_child_blocks,
+            bucket_capacity,
+            packing_factor,
+            recursion_threshold,
+        );
+        let child = PathORAM::with_position_map(num_child_blocks, bucket_capacity, child_position_map);
+
+        RecursivePositionMap {
+            child,
+            packing_factor: packing_factor_u32,
+        }
+    }
+
+    fn locate(&self, block_id: u32) -> (u32, usize) {
+        let child_block_id = (block_id - 1) / self.packing_factor + 1;
+        let offset = ((block_id - 1) % self.packing_factor) as usize;
+        (child_block_id, offset)
+    }
+
+    /// Reads the packed position array holding `block_id`'s entry, treating
+    /// an empty (never-written) child block as all-zero positions.
+    fn read_positions(&mut self, child_block_id: u32) -> Result<Vec<u32>, OramError> {
+        let payload = self.child.access("read", child_block_id, None)?;
+        Ok(positions_from_payload(payload.as_deref(), self.packing_factor as usize))
+    }
+}
+
+/// Decodes a packed position array, treating an empty (never-written) child
+/// block as all-zero positions. Shared by `read_positions` and `set`, which
+/// derives its update from the same payload via `PathORAM::update`.
+fn positions_from_payload(payload: Option<&[u8]>, packing_factor: usize) -> Vec<u32> {
+    match payload {
+        Some(bytes) if !bytes.is_empty() => decode_positions(bytes),
+        _ => vec![0u32; packing_factor],
+    }
+}
+
+impl PositionMap for RecursivePositionMap {
+    fn get(&mut self, block_id: u32) -> Result<u32, OramError> {
+        let (child_block_id, offset) = self.locate(block_id);
+        Ok(self.read_positions(child_block_id)?[offset])
+    }
+
+    fn set(&mut self, block_id: u32, position: u32) -> Result<(), OramError> {
+        let (child_block_id, offset) = self.locate(block_id);
+        let packing_factor = self.packing_factor as usize;
+        // A single child `update` read-modify-writes the packed array in one
+        // tree traversal, instead of a `read` access followed by a separate
+        // `write` access each doing their own full traversal.
+        self.child.update(child_block_id, move |old_payload| {
+            let mut positions = positions_from_payload(old_payload.as_deref(), packing_factor);
+            positions[offset] = position;
+            encode_positions(&positions)
+        })?;
+        Ok(())
+    }
+}
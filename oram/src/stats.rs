@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Stash and access counters for a `PathORAM`, updated on every `access`
+/// when the `stats` feature is enabled (otherwise they stay at zero).
+/// Inspired by Solana's `BucketStats`/`bucket_stats`, the counters live
+/// behind atomics (and a `Mutex` for the histogram) so recording them costs
+/// little even on the hot path.
+#[derive(Default)]
+pub struct OramStats {
+    reads: AtomicU64,
+    writes: AtomicU64,
+    buckets_touched: AtomicU64,
+    evictions_attempted: AtomicU64,
+    evictions_succeeded: AtomicU64,
+    current_stash_occupancy: AtomicU64,
+    max_stash_occupancy: AtomicU64,
+    stash_histogram: Mutex<BTreeMap<usize, u64>>,
+}
+
+impl OramStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record_op(&self, op: &str) {
+        if op == "write" {
+            self.writes.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.reads.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_bucket_touched(&self) {
+        self.buckets_touched.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_eviction(&self, succeeded: bool) {
+        self.evictions_attempted.fetch_add(1, Ordering::Relaxed);
+        if succeeded {
+            self.evictions_succeeded.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_stash_size(&self, size: usize) {
+        let size = size as u64;
+        self.current_stash_occupancy.store(size, Ordering::Relaxed);
+        self.max_stash_occupancy.fetch_max(size, Ordering::Relaxed);
+        *self
+            .stash_histogram
+            .lock()
+            .unwrap()
+            .entry(size as usize)
+            .or_insert(0) += 1;
+    }
+
+    /// Returns a point-in-time copy of the counters.
+    pub fn snapshot(&self) -> OramStatsSnapshot {
+        OramStatsSnapshot {
+            reads: self.reads.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            buckets_touched: self.buckets_touched.load(Ordering::Relaxed),
+            evictions_attempted: self.evictions_attempted.load(Ordering::Relaxed),
+            evictions_succeeded: self.evictions_succeeded.load(Ordering::Relaxed),
+            current_stash_occupancy: self.current_stash_occupancy.load(Ordering::Relaxed),
+            max_stash_occupancy: self.max_stash_occupancy.load(Ordering::Relaxed),
+            stash_histogram: self.stash_histogram.lock().unwrap().clone(),
+        }
+    }
+
+    /// Clears every counter, e.g. to discard a warmup period before
+    /// measuring steady-state behavior.
+    pub fn reset(&self) {
+        self.reads.store(0, Ordering::Relaxed);
+        self.writes.store(0, Ordering::Relaxed);
+        self.buckets_touched.store(0, Ordering::Relaxed);
+        self.evictions_attempted.store(0, Ordering::Relaxed);
+        self.evictions_succeeded.store(0, Ordering::Relaxed);
+        self.current_stash_occupancy.store(0, Ordering::Relaxed);
+        self.max_stash_occupancy.store(0, Ordering::Relaxed);
+        self.stash_histogram.lock().unwrap().clear();
+    }
+}
+
+/// A point-in-time copy of [`OramStats`]'s counters, returned by
+/// `PathORAM::stats`.
+#[derive(Clone, Debug, Default)]
+pub struct OramStatsSnapshot {
+    pub reads: u64,
+    pub writes: u64,
+    pub buckets_touched: u64,
+    pub evictions_attempted: u64,
+    pub evictions_succeeded: u64,
+    pub current_stash_occupancy: u64,
+    pub max_stash_occupancy: u64,
+    pub stash_histogram: BTreeMap<usize, u64>,
+}
+
+impl OramStatsSnapshot {
+    /// Produces the `(R, count)` overflow-probability table: row `(-1, total)`
+    /// followed by one row for each stash size `R` from `0` up to the largest
+    /// observed size, where `count` is the
+    /// number of accesses whose final stash size was `>= R`. This is the
+    /// exact table the benchmark and `plotters` binaries used to hand-derive
+    /// via a `running_sum` loop.
+    pub fn to_csv(&self) -> String {
+        let total: u64 = self.stash_histogram.values().sum();
+        let max_size = self.stash_histogram.keys().next_back().copied().unwrap_or(0);
+
+        let mut rows = vec![(-1_i64, total)];
+        let mut running_sum = total;
+        for size in 0..=max_size {
+            if running_sum == 0 {
+                break;
+            }
+            rows.push((size as i64, running_sum));
+            running_sum -= self.stash_histogram.get(&size).copied().unwrap_or(0);
+        }
+
+        let mut csv = String::new();
+        for (r, count) in rows {
+            if count > 0 {
+                csv.push_str(&format!("{r},{count}\n"));
+            }
+        }
+        csv
+    }
+}
@@ -0,0 +1,284 @@
+use crate::block::CompressionType;
+use crate::bucket::Bucket;
+
+#[cfg(feature = "mmap")]
+use memmap2::MmapMut;
+#[cfg(feature = "mmap")]
+use std::fs::OpenOptions;
+#[cfg(feature = "mmap")]
+use std::path::Path;
+
+/// Storage backend for the ORAM tree, abstracting over where buckets
+/// physically live so `PathORAM` never indexes a tree directly.
+///
+/// The eviction/stash logic in `PathORAM::access` is unchanged by the
+/// choice of backend: it only ever reads or writes one bucket at a time.
+pub trait TreeStorage {
+    /// Reads the bucket at `index`.
+    fn read_bucket(&self, index: usize) -> Bucket;
+
+    /// Overwrites the bucket at `index`.
+    fn write_bucket(&mut self, index: usize, bucket: &Bucket);
+
+    /// The number of buckets held by this backend.
+    fn len(&self) -> usize;
+
+    /// Whether this backend holds no buckets.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Grows the backend to hold `new_len` buckets, allocating the new ones
+    /// empty. Used by [`crate::oram::PathORAM::resize`] to add a tree level
+    /// without rebuilding the existing buckets.
+    fn grow_to(&mut self, new_len: usize);
+}
+
+/// Default, in-memory backend: the whole tree lives in a `Vec<Bucket>`.
+///
+/// Bounded by available RAM, but avoids file I/O entirely, so it is the
+/// right choice for tests and for trees small enough to fit in memory.
+pub struct MemoryStorage {
+    buckets: Vec<Bucket>,
+    capacity: usize,
+    compression: CompressionType,
+}
+
+impl MemoryStorage {
+    pub fn new(num_buckets: usize, capacity: usize, compression: CompressionType) -> Self {
+        let mut buckets = Vec::with_capacity(num_buckets);
+        for _ in 0..num_buckets {
+            buckets.push(Bucket::new(capacity, compression));
+        }
+        MemoryStorage {
+            buckets,
+            capacity,
+            compression,
+        }
+    }
+}
+
+impl TreeStorage for MemoryStorage {
+    fn read_bucket(&self, index: usize) -> Bucket {
+        self.buckets[index].clone()
+    }
+
+    fn write_bucket(&mut self, index: usize, bucket: &Bucket) {
+        self.buckets[index] = bucket.clone();
+    }
+
+    fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    fn grow_to(&mut self, new_len: usize) {
+        while self.buckets.len() < new_len {
+            self.buckets.push(Bucket::new(self.capacity, self.compression));
+        }
+    }
+}
+
+/// Memory-mapped file backend, modeled on the value-table design used by
+/// parity-db and Solana's `bucket_storage`: `num_buckets` contiguous
+/// fixed-size slots of [`Bucket::slot_size`]`(capacity)` bytes (one
+/// occupancy byte plus `BLOCK_SIZE` bytes per block slot, then an 8-byte
+/// checksum) in a single file, so a
+/// bucket is read or written by seeking to `index * slot_size` rather than
+/// by holding the whole tree in RAM.
+#[cfg(feature = "mmap")]
+pub struct MmapStorage {
+    file: std::fs::File,
+    mmap: MmapMut,
+    capacity: usize,
+    compression: CompressionType,
+    num_buckets: usize,
+    slot_size: usize,
+}
+
+#[cfg(feature = "mmap")]
+impl MmapStorage {
+    /// Creates (or truncates) the backing file at `path` and maps
+    /// `num_buckets` empty slots of [`Bucket::slot_size`]`(capacity)` bytes each.
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        num_buckets: usize,
+        capacity: usize,
+        compression: CompressionType,
+    ) -> std::io::Result<Self> {
+        let slot_size = Bucket::slot_size(capacity);
+        let file_len = (num_buckets * slot_size) as u64;
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(file_len)?;
+
+        let mut mmap = unsafe { MmapMut::map_mut(&file)? };
+
+        // Initialize every slot to an empty bucket so reads before the
+        // first write see a well-formed (all-dummy) bucket.
+        let empty_slot = Bucket::new(capacity, compression)
+            .to_bytes()
+            .expect("an empty bucket always fits in its own slot");
+        for index in 0..num_buckets {
+            let start = index * slot_size;
+            mmap[start..start + slot_size].copy_from_slice(&empty_slot);
+        }
+
+        Ok(MmapStorage {
+            file,
+            mmap,
+            capacity,
+            compression,
+            num_buckets,
+            slot_size,
+        })
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl TreeStorage for MmapStorage {
+    fn read_bucket(&self, index: usize) -> Bucket {
+        let start = index * self.slot_size;
+        Bucket::from_bytes(
+            &self.mmap[start..start + self.slot_size],
+            self.capacity,
+            self.compression,
+        )
+        .expect("bucket slot was corrupted")
+    }
+
+    fn write_bucket(&mut self, index: usize, bucket: &Bucket) {
+        let start = index * self.slot_size;
+        let bytes = bucket
+            .to_bytes()
+            .expect("bucket contents always fit in their own slot");
+        self.mmap[start..start + self.slot_size].copy_from_slice(&bytes);
+    }
+
+    fn len(&self) -> usize {
+        self.num_buckets
+    }
+
+    fn grow_to(&mut self, new_len: usize) {
+        if new_len <= self.num_buckets {
+            return;
+        }
+
+        let old_len = self.num_buckets;
+        let new_file_len = (new_len * self.slot_size) as u64;
+        self.file
+            .set_len(new_file_len)
+            .expect("failed to extend mmap-backed tree file");
+        self.mmap = unsafe {
+            MmapMut::map_mut(&self.file).expect("failed to remap mmap-backed tree file")
+        };
+
+        let empty_slot = Bucket::new(self.capacity, self.compression)
+            .to_bytes()
+            .expect("an empty bucket always fits in its own slot");
+        for index in old_len..new_len {
+            let start = index * self.slot_size;
+            self.mmap[start..start + self.slot_size].copy_from_slice(&empty_slot);
+        }
+
+        self.num_buckets = new_len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+
+    #[test]
+    fn test_memory_storage_read_write_round_trip() {
+        let mut storage = MemoryStorage::new(4, 2, CompressionType::None);
+        let mut bucket = storage.read_bucket(1);
+        bucket
+            .add_block(Block {
+                block_id: 7,
+                data: vec![1, 2, 3],
+            })
+            .unwrap();
+        storage.write_bucket(1, &bucket);
+
+        assert_eq!(storage.read_bucket(1).get_block(7).unwrap().data, vec![1, 2, 3]);
+        // Other slots are untouched.
+        assert!(storage.read_bucket(0).get_block(7).is_none());
+    }
+
+    #[test]
+    fn test_memory_storage_grow_to_preserves_existing_buckets() {
+        let mut storage = MemoryStorage::new(2, 2, CompressionType::None);
+        let mut bucket = storage.read_bucket(0);
+        bucket
+            .add_block(Block {
+                block_id: 1,
+                data: vec![9],
+            })
+            .unwrap();
+        storage.write_bucket(0, &bucket);
+
+        storage.grow_to(5);
+
+        assert_eq!(storage.len(), 5);
+        assert_eq!(storage.read_bucket(0).get_block(1).unwrap().data, vec![9]);
+        assert!(storage.read_bucket(4).get_block(1).is_none());
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_mmap_storage_read_write_round_trip() {
+        let path = std::env::temp_dir().join(format!(
+            "oram-mmap-storage-test-{}-{:?}",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        let mut storage = MmapStorage::new(&path, 4, 2, CompressionType::None).unwrap();
+
+        let mut bucket = storage.read_bucket(1);
+        bucket
+            .add_block(Block {
+                block_id: 7,
+                data: vec![1, 2, 3],
+            })
+            .unwrap();
+        storage.write_bucket(1, &bucket);
+
+        assert_eq!(storage.read_bucket(1).get_block(7).unwrap().data, vec![1, 2, 3]);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_mmap_storage_grow_to_preserves_existing_buckets() {
+        let path = std::env::temp_dir().join(format!(
+            "oram-mmap-storage-grow-test-{}-{:?}",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        let mut storage = MmapStorage::new(&path, 2, 2, CompressionType::None).unwrap();
+
+        let mut bucket = storage.read_bucket(0);
+        bucket
+            .add_block(Block {
+                block_id: 1,
+                data: vec![9],
+            })
+            .unwrap();
+        storage.write_bucket(0, &bucket);
+
+        storage.grow_to(5);
+
+        assert_eq!(storage.len(), 5);
+        assert_eq!(storage.read_bucket(0).get_block(1).unwrap().data, vec![9]);
+        assert!(storage.read_bucket(4).get_block(1).is_none());
+
+        std::fs::remove_file(&path).ok();
+    }
+}